@@ -1,13 +1,14 @@
 use crossterm::{
     cursor::{Hide, MoveTo, Show},
     event::{read, Event, KeyCode},
-    execute,
+    execute, queue,
     style::Print,
     terminal::{Clear, ClearType, DisableLineWrap, EnableLineWrap},
 };
+use serde::Deserialize;
 use shlex;
 use std::fs::File;
-use std::io::{self, BufRead, BufReader};
+use std::io::{self, BufRead, BufReader, Write};
 use std::path::{Path, PathBuf};
 use std::process::Command;
 
@@ -16,16 +17,151 @@ struct MenuItem {
     name: String,
     working_dir: String,
     command: Vec<String>,
+    capture: bool,
+    generator: bool,
+}
+
+// One entry of a generator's JSON output, deserialized straight into the
+// fields needed to build a transient `MenuItem`.
+#[derive(Deserialize)]
+struct GeneratorEntry {
+    name: String,
+    working_dir: String,
+    command: String,
 }
 
 struct Menu {
     items: Vec<MenuItem>,
     selected: usize,
     max_length: usize,
+    scroll_offset: usize,
+    query: String,
+    // Indices into `items` for the currently visible subset, in display order.
+    filtered: Vec<usize>,
 }
 
 const MENU_FILE: &str = "menu.csv";
 
+// Keep the selection at least this many rows from the viewport edges, unless
+// the viewport is too short to afford it.
+const SCROLL_PADDING: usize = 2;
+
+// Fuzzy subsequence match of `query` against `name`. Returns `None` if `name`
+// doesn't contain every character of `query` in order, otherwise a score that
+// rewards consecutive matches and word-boundary matches and penalizes gaps
+// between matched characters.
+fn fuzzy_match(query: &str, name: &str) -> Option<i32> {
+    if query.is_empty() {
+        return Some(0);
+    }
+
+    let query_chars: Vec<char> = query.to_lowercase().chars().collect();
+    let name_chars: Vec<char> = name.to_lowercase().chars().collect();
+
+    let mut qi = 0;
+    let mut score = 0i32;
+    let mut consecutive = 0i32;
+    let mut last_match: Option<usize> = None;
+
+    for (ni, &nc) in name_chars.iter().enumerate() {
+        if qi >= query_chars.len() {
+            break;
+        }
+        if nc != query_chars[qi] {
+            continue;
+        }
+
+        if let Some(last) = last_match {
+            let gap = ni - last - 1;
+            if gap == 0 {
+                consecutive += 1;
+                score += consecutive * 5;
+            } else {
+                consecutive = 0;
+                score -= gap as i32;
+            }
+        }
+
+        let at_boundary = ni == 0 || matches!(name_chars[ni - 1], ' ' | '_' | '-');
+        if at_boundary {
+            score += 10;
+        }
+
+        last_match = Some(ni);
+        qi += 1;
+    }
+
+    (qi == query_chars.len()).then_some(score)
+}
+
+// Puts the terminal into menu mode (raw, hidden cursor, no line wrap) on
+// construction and guarantees it's put back to normal on every exit path —
+// including a panic — instead of relying on manual enable/disable calls
+// scattered across every function that touches the terminal.
+struct TerminalGuard;
+
+impl TerminalGuard {
+    fn new() -> io::Result<Self> {
+        crossterm::terminal::enable_raw_mode()?;
+        execute!(io::stdout(), Hide, DisableLineWrap)?;
+        Ok(TerminalGuard)
+    }
+}
+
+impl Drop for TerminalGuard {
+    fn drop(&mut self) {
+        let _ = execute!(io::stdout(), Show, EnableLineWrap, Clear(ClearType::All));
+        let _ = crossterm::terminal::disable_raw_mode();
+    }
+}
+
+// Temporarily hands the terminal back to normal mode (visible cursor, line
+// wrap, not raw) for the duration of the guard, then restores menu mode on
+// drop. Used whenever a child process needs the real terminal or an error
+// message needs to be printed with normal line handling.
+struct SuspendedTerminal;
+
+impl SuspendedTerminal {
+    fn new() -> io::Result<Self> {
+        execute!(
+            io::stdout(),
+            Show,
+            EnableLineWrap,
+            Clear(ClearType::All),
+            MoveTo(0, 0)
+        )?;
+        crossterm::terminal::disable_raw_mode()?;
+        Ok(SuspendedTerminal)
+    }
+}
+
+impl Drop for SuspendedTerminal {
+    fn drop(&mut self) {
+        let _ = crossterm::terminal::enable_raw_mode();
+        let _ = execute!(io::stdout(), Hide, DisableLineWrap);
+    }
+}
+
+// Briefly enables raw mode so a keypress can be consumed without it being
+// echoed to the now-normal terminal, then disables it again.
+fn wait_for_keypress() -> io::Result<()> {
+    crossterm::terminal::enable_raw_mode()?;
+    let result = read();
+    crossterm::terminal::disable_raw_mode()?;
+    result.map(|_| ())
+}
+
+// Restore the terminal before a panic's default message is printed, so a
+// crash never leaves the terminal in raw mode with a hidden cursor.
+fn install_panic_hook() {
+    let default_hook = std::panic::take_hook();
+    std::panic::set_hook(Box::new(move |info| {
+        let _ = execute!(io::stdout(), Show, EnableLineWrap, Clear(ClearType::All));
+        let _ = crossterm::terminal::disable_raw_mode();
+        default_hook(info);
+    }));
+}
+
 fn expand_tilde<P: AsRef<Path>>(path: P) -> io::Result<PathBuf> {
     let path_str = path.as_ref().to_string_lossy();
     if path_str.starts_with('~') {
@@ -57,11 +193,21 @@ impl MenuItem {
             } else {
                 shlex::split(parts[2].trim())?
             };
+            let capture = parts
+                .get(3)
+                .map(|flag| matches!(flag.trim(), "1" | "true" | "capture"))
+                .unwrap_or(false);
+            let generator = parts
+                .get(4)
+                .map(|flag| matches!(flag.trim(), "1" | "true" | "generator"))
+                .unwrap_or(false);
 
             Some(MenuItem {
                 name,
                 working_dir,
                 command,
+                capture,
+                generator,
             })
         } else {
             None
@@ -83,6 +229,9 @@ impl Menu {
             items: Vec::new(),
             selected: 0,
             max_length: 0,
+            scroll_offset: 0,
+            query: String::new(),
+            filtered: Vec::new(),
         }
     }
 
@@ -105,54 +254,148 @@ impl Menu {
             }
         }
 
+        menu.apply_filter();
         Ok(menu)
     }
 
-    fn draw(&self) -> io::Result<()> {
-        let mut stdout = io::stdout();
-        execute!(stdout, Clear(ClearType::All), MoveTo(0, 0))?;
+    // Re-derive `filtered` from `query`, ranking fuzzy matches by descending
+    // score, and reset the selection since the visible set just changed.
+    fn apply_filter(&mut self) {
+        self.filtered = if self.query.is_empty() {
+            (0..self.items.len()).collect()
+        } else {
+            let mut scored: Vec<(usize, i32)> = self
+                .items
+                .iter()
+                .enumerate()
+                .filter_map(|(i, item)| fuzzy_match(&self.query, &item.name).map(|score| (i, score)))
+                .collect();
+            scored.sort_by_key(|&(_, score)| std::cmp::Reverse(score));
+            scored.into_iter().map(|(i, _)| i).collect()
+        };
+        self.selected = 0;
+        self.scroll_offset = 0;
+    }
 
+    // Number of item rows drawn between the top and bottom borders: as many as
+    // fit in the terminal, but no more than the filtered items, and at least 1.
+    fn visible_rows(&self) -> io::Result<usize> {
         let term_size = crossterm::terminal::size()?;
-        let center_x = (term_size.0 as usize - self.max_length) / 2;
+        let usable = (term_size.1 as usize).saturating_sub(2);
+        Ok(usable.max(1).min(self.filtered.len().max(1)))
+    }
 
-        // Draw top border (single line)
-        let top_border = format!("┌{}┐", "─".repeat(self.max_length - 2));
-        execute!(stdout, MoveTo(center_x as u16, 0), Print(top_border))?;
+    // Clamp the scroll-padding down when the viewport is too short to afford
+    // the full margin on both edges.
+    fn scroll_padding(&self, visible_rows: usize) -> usize {
+        SCROLL_PADDING.min(visible_rows / 2)
+    }
 
-        // Draw menu items
-        for (i, item) in self.items.iter().enumerate() {
-            let (left_border, right_border) = if i == self.selected {
+    // Build a top/bottom border, splitting the dash run around an optional
+    // scroll indicator. The split can never underflow, and the indicator is
+    // simply omitted when `max_length` is too small to fit it.
+    fn border_with_indicator(&self, left_corner: char, right_corner: char, indicator: Option<char>) -> String {
+        let inner = self.max_length.saturating_sub(2);
+        if let Some(arrow) = indicator {
+            if self.max_length >= 5 {
+                let avail = self.max_length.saturating_sub(3);
+                let left = avail / 2;
+                let right = avail - left;
+                return format!(
+                    "{}{}{}{}{}",
+                    left_corner,
+                    "─".repeat(left),
+                    arrow,
+                    "─".repeat(right),
+                    right_corner
+                );
+            }
+        }
+        format!("{}{}{}", left_corner, "─".repeat(inner), right_corner)
+    }
+
+    // Keep `selected` at least `scroll_padding` rows away from the viewport
+    // edges, scrolling the window just enough to restore the margin.
+    fn adjust_scroll(&mut self) -> io::Result<()> {
+        let visible_rows = self.visible_rows()?;
+        let pad = self.scroll_padding(visible_rows);
+        let max_offset = self.filtered.len().saturating_sub(visible_rows);
+
+        if self.selected < self.scroll_offset + pad {
+            self.scroll_offset = self.selected.saturating_sub(pad);
+        } else if self.selected > self.scroll_offset + visible_rows - 1 - pad {
+            self.scroll_offset = self.selected + pad + 1 - visible_rows;
+        }
+        self.scroll_offset = self.scroll_offset.min(max_offset);
+        Ok(())
+    }
+
+    // Build the full frame with `queue!` and flush once, instead of issuing
+    // one flushing `execute!` per element, to avoid flicker on slow/SSH
+    // terminals.
+    fn draw(&self) -> io::Result<()> {
+        let mut stdout = io::stdout();
+        queue!(stdout, Clear(ClearType::All), MoveTo(0, 0))?;
+
+        let term_size = crossterm::terminal::size()?;
+        let center_x = (term_size.0 as usize).saturating_sub(self.max_length) / 2;
+        let visible_rows = self.visible_rows()?;
+        let end = (self.scroll_offset + visible_rows).min(self.filtered.len());
+        let has_above = self.scroll_offset > 0;
+        let has_below = end < self.filtered.len();
+
+        // Draw top border (single line), with an indicator if items are scrolled off above.
+        let top_border = self.border_with_indicator('┌', '┐', has_above.then_some('↑'));
+        queue!(stdout, MoveTo(center_x as u16, 0), Print(top_border))?;
+
+        // Draw visible menu items
+        for (row, pos) in (self.scroll_offset..end).enumerate() {
+            let item = &self.items[self.filtered[pos]];
+            let (left_border, right_border) = if pos == self.selected {
                 ("║", "║") // Double line for selected item
             } else {
                 ("│", "│") // Single line for unselected items
             };
 
-            let padding = self.max_length - 2;
-            let name_padding = (padding - item.name.len()) / 2;
+            let padding = self.max_length.saturating_sub(2);
+            let name_padding = padding.saturating_sub(item.name.len()) / 2;
             let line = format!(
                 "{}{}{}{}{}",
                 left_border,
                 " ".repeat(name_padding),
                 item.name,
-                " ".repeat(padding - name_padding - item.name.len()),
+                " ".repeat(padding.saturating_sub(name_padding + item.name.len())),
                 right_border
             );
-            execute!(stdout, MoveTo(center_x as u16, (i + 1) as u16), Print(line))?;
+            queue!(stdout, MoveTo(center_x as u16, (row + 1) as u16), Print(line))?;
         }
 
-        // Draw bottom border (single line)
-        let bottom_border = format!("└{}┘", "─".repeat(self.max_length - 2));
-        execute!(
+        // Draw bottom border (single line): shows the active search query if
+        // one is being typed, otherwise an indicator if items are scrolled
+        // off below.
+        let inner = self.max_length.saturating_sub(2);
+        let bottom_border = if !self.query.is_empty() {
+            let label = format!("/{}", self.query);
+            let label: String = label.chars().take(inner).collect();
+            format!("└{}{}┘", label, "─".repeat(inner - label.chars().count()))
+        } else {
+            self.border_with_indicator('└', '┘', has_below.then_some('↓'))
+        };
+        queue!(
             stdout,
-            MoveTo(center_x as u16, (self.items.len() + 1) as u16),
+            MoveTo(center_x as u16, (visible_rows + 1) as u16),
             Print(bottom_border)
         )?;
 
-        Ok(())
+        stdout.flush()
     }
 
     fn run_selected(&self) -> io::Result<()> {
-        if let Some(item) = self.items.get(self.selected) {
+        if let Some(item) = self
+            .filtered
+            .get(self.selected)
+            .and_then(|&i| self.items.get(i))
+        {
             if item.is_submenu() {
                 let expanded_dir = item.get_expanded_working_dir()?;
                 let submenu_path = expanded_dir.join(MENU_FILE);
@@ -164,36 +407,35 @@ impl Menu {
                 return Ok(());
             }
 
-            // Properly restore terminal state before running command
-            execute!(
-                io::stdout(),
-                Show,
-                EnableLineWrap,
-                Clear(ClearType::All),
-                MoveTo(0, 0)
-            )?;
-            crossterm::terminal::disable_raw_mode()?;
+            if item.generator {
+                return self.run_generator(item);
+            }
+
+            if item.capture {
+                return self.run_captured(item);
+            }
 
             if let Some(program) = item.command.first() {
                 let args = item.command.iter().skip(1);
                 let expanded_dir = item.get_expanded_working_dir()?;
-                let status = Command::new(program)
-                    .args(args)
-                    .current_dir(&expanded_dir)
-                    .status()
-                    .map_err(|e| {
-                        io::Error::new(e.kind(), format!("Failed to execute '{}': {}", program, e))
-                    })?;
-
-                // After command completes, wait for any key before restoring menu state
-                println!("\nPress any key to continue...");
-                crossterm::terminal::enable_raw_mode()?;
-                read()?;
-                crossterm::terminal::disable_raw_mode()?;
-
-                // Restore terminal state for menu
-                crossterm::terminal::enable_raw_mode()?;
-                execute!(io::stdout(), Hide, DisableLineWrap)?;
+
+                let status = {
+                    let _suspend = SuspendedTerminal::new()?;
+                    let status = Command::new(program)
+                        .args(args)
+                        .current_dir(&expanded_dir)
+                        .status()
+                        .map_err(|e| {
+                            io::Error::new(
+                                e.kind(),
+                                format!("Failed to execute '{}': {}", program, e),
+                            )
+                        })?;
+
+                    println!("\nPress any key to continue...");
+                    wait_for_keypress()?;
+                    status
+                };
 
                 if !status.success() {
                     self.show_error(&format!(
@@ -206,48 +448,210 @@ impl Menu {
         Ok(())
     }
 
-    fn show_error(&self, message: &str) -> io::Result<()> {
-        // Temporarily restore normal terminal state
-        execute!(
-            io::stdout(),
-            Clear(ClearType::All),
-            EnableLineWrap,
-            Show,
-            MoveTo(0, 0)
-        )?;
-        crossterm::terminal::disable_raw_mode()?;
+    // Run a `generator`-flagged item's command, parse its stdout as a JSON
+    // array of `{name, working_dir, command}` entries, and navigate the
+    // resulting transient menu exactly like a file-backed submenu.
+    fn run_generator(&self, item: &MenuItem) -> io::Result<()> {
+        let Some(program) = item.command.first() else {
+            return Ok(());
+        };
+        let args = item.command.iter().skip(1);
+        let expanded_dir = item.get_expanded_working_dir()?;
+
+        let output = match Command::new(program).args(args).current_dir(&expanded_dir).output() {
+            Ok(output) => output,
+            Err(e) => {
+                return self.show_error(&format!("Failed to run generator '{}': {}", program, e))
+            }
+        };
 
-        println!("Error: {}\nPress any key to continue...", message);
-        crossterm::terminal::enable_raw_mode()?;
-        read()?;
-        crossterm::terminal::disable_raw_mode()?;
+        if !output.status.success() {
+            return self.show_error(&format!(
+                "Generator '{}' exited with status: {}",
+                item.name,
+                output.status.code().unwrap_or(-1)
+            ));
+        }
+
+        let entries: Vec<GeneratorEntry> = match serde_json::from_slice(&output.stdout) {
+            Ok(entries) => entries,
+            Err(e) => {
+                return self.show_error(&format!(
+                    "Failed to parse output of generator '{}': {}",
+                    item.name, e
+                ))
+            }
+        };
+
+        if entries.is_empty() {
+            return self.show_error(&format!("Generator '{}' returned no items", item.name));
+        }
+
+        let mut submenu = Menu::new();
+        for entry in entries {
+            let command = if entry.command.trim().is_empty() {
+                Vec::new()
+            } else {
+                shlex::split(entry.command.trim()).unwrap_or_default()
+            };
+            submenu.max_length = submenu.max_length.max(entry.name.len() + 2);
+            submenu.items.push(MenuItem {
+                name: entry.name,
+                working_dir: entry.working_dir,
+                command,
+                capture: false,
+                generator: false,
+            });
+        }
+        submenu.apply_filter();
+        submenu.run()
+    }
+
+    // Run a `capture`-flagged item without handing over the terminal: the
+    // child's stdout/stderr are collected and shown in a scrollable pane
+    // drawn in the same box-drawing style as the menu itself.
+    fn run_captured(&self, item: &MenuItem) -> io::Result<()> {
+        let Some(program) = item.command.first() else {
+            return Ok(());
+        };
+        let args = item.command.iter().skip(1);
+        let expanded_dir = item.get_expanded_working_dir()?;
+
+        let output = Command::new(program)
+            .args(args)
+            .current_dir(&expanded_dir)
+            .output();
+
+        let text: Result<String, io::Error> = output.map(|out| {
+            let mut combined = String::from_utf8_lossy(&out.stdout).into_owned();
+            if !out.stderr.is_empty() {
+                combined.push_str(&String::from_utf8_lossy(&out.stderr));
+            }
+            combined
+        });
+
+        self.show_output_pane(&item.name, text)
+    }
+
+    // Render captured command output (or the error that prevented capturing
+    // it) in a bordered, scrollable pane until the user presses Esc.
+    fn show_output_pane(&self, title: &str, text: Result<String, io::Error>) -> io::Result<()> {
+        let content = match &text {
+            Ok(s) => s.clone(),
+            Err(e) => format!("Failed to run '{}': {}", title, e),
+        };
+
+        let term_size = crossterm::terminal::size()?;
+        let width = (term_size.0 as usize).saturating_sub(4).max(10);
+        let height = (term_size.1 as usize).saturating_sub(2).max(1);
+
+        let mut lines: Vec<String> = Vec::new();
+        for raw_line in content.split('\n') {
+            if raw_line.is_empty() {
+                lines.push(String::new());
+                continue;
+            }
+            let chars: Vec<char> = raw_line.chars().collect();
+            for chunk in chars.chunks(width) {
+                lines.push(chunk.iter().collect());
+            }
+        }
+        if lines.is_empty() {
+            lines.push(String::new());
+        }
+
+        let mut scroll: usize = 0;
+        let max_scroll = lines.len().saturating_sub(height);
+
+        loop {
+            let mut stdout = io::stdout();
+            queue!(stdout, Clear(ClearType::All), MoveTo(0, 0))?;
+
+            let border = format!("┌{}┐", "─".repeat(width + 2));
+            queue!(stdout, MoveTo(0, 0), Print(border))?;
+
+            for row in 0..height {
+                let line = lines.get(scroll + row).map(String::as_str).unwrap_or("");
+                let padded = format!("│ {:<width$} │", line, width = width);
+                queue!(stdout, MoveTo(0, (row + 1) as u16), Print(padded))?;
+            }
+
+            let bottom = format!("└{}┘", "─".repeat(width + 2));
+            queue!(stdout, MoveTo(0, (height + 1) as u16), Print(bottom))?;
+
+            stdout.flush()?;
+
+            if let Event::Key(event) = read()? {
+                match event.code {
+                    KeyCode::Up => scroll = scroll.saturating_sub(1),
+                    KeyCode::Down => scroll = (scroll + 1).min(max_scroll),
+                    KeyCode::PageUp => scroll = scroll.saturating_sub(height),
+                    KeyCode::PageDown => scroll = (scroll + height).min(max_scroll),
+                    KeyCode::Esc => break,
+                    _ => {}
+                }
+            }
+        }
 
-        // Restore menu terminal state
-        crossterm::terminal::enable_raw_mode()?;
-        execute!(io::stdout(), Hide, DisableLineWrap)?;
         Ok(())
     }
 
+    fn show_error(&self, message: &str) -> io::Result<()> {
+        let _suspend = SuspendedTerminal::new()?;
+        println!("Error: {}\nPress any key to continue...", message);
+        wait_for_keypress()
+    }
+
     fn run(&mut self) -> io::Result<()> {
+        // Only the selection, scroll offset, and filter query affect what
+        // `draw` renders; skip the redraw when a keypress didn't touch any
+        // of them (e.g. an unmapped key) to cut flicker further.
+        let mut last_frame: Option<(usize, usize, String)> = None;
+
         loop {
-            self.draw()?;
+            let frame_state = (self.selected, self.scroll_offset, self.query.clone());
+            if last_frame.as_ref() != Some(&frame_state) {
+                self.draw()?;
+                last_frame = Some(frame_state);
+            }
 
             match read()? {
                 Event::Key(event) => match event.code {
                     KeyCode::Up if self.selected > 0 => {
                         self.selected -= 1;
+                        self.adjust_scroll()?;
                     }
-                    KeyCode::Down if self.selected < self.items.len() - 1 => {
+                    KeyCode::Down if self.selected + 1 < self.filtered.len() => {
                         self.selected += 1;
+                        self.adjust_scroll()?;
                     }
                     KeyCode::Enter => {
                         self.run_selected()?;
+                        // Running the item may have drawn over the screen
+                        // (error pane, output pane, child program); force a
+                        // redraw even if our own state didn't change.
+                        last_frame = None;
+                    }
+                    KeyCode::Backspace if !self.query.is_empty() => {
+                        self.query.pop();
+                        self.apply_filter();
+                    }
+                    KeyCode::Esc if !self.query.is_empty() => {
+                        self.query.clear();
+                        self.apply_filter();
                     }
                     KeyCode::Esc => {
                         break;
                     }
+                    KeyCode::Char(c) => {
+                        self.query.push(c);
+                        self.apply_filter();
+                    }
                     _ => {}
                 },
+                Event::Resize(..) => {
+                    last_frame = None;
+                }
                 _ => {}
             }
         }
@@ -256,11 +660,10 @@ impl Menu {
 }
 
 fn main() -> io::Result<()> {
-    // Set up terminal
-    crossterm::terminal::enable_raw_mode()?;
-    execute!(io::stdout(), Hide, DisableLineWrap)?;
+    install_panic_hook();
 
     // Ensure cleanup happens even if we panic
+    let _guard = TerminalGuard::new()?;
     let result = std::panic::catch_unwind(|| {
         if let Ok(mut menu) = Menu::load_from_file(MENU_FILE) {
             menu.run()
@@ -269,10 +672,6 @@ fn main() -> io::Result<()> {
         }
     });
 
-    // Clean up terminal state
-    execute!(io::stdout(), Show, EnableLineWrap)?;
-    crossterm::terminal::disable_raw_mode()?;
-
     // Handle any errors or panics
     match result {
         Ok(Ok(())) => Ok(()),
@@ -280,3 +679,27 @@ fn main() -> io::Result<()> {
         Err(_) => Err(io::Error::new(io::ErrorKind::Other, "Program panicked")),
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn fuzzy_match_ranks_exact_above_scattered() {
+        let exact = fuzzy_match("menu", "menu").unwrap();
+        let scattered = fuzzy_match("menu", "my evil nightly update").unwrap();
+        assert!(exact > scattered);
+    }
+
+    #[test]
+    fn fuzzy_match_rewards_word_boundary_matches() {
+        let boundary = fuzzy_match("db", "db_connect").unwrap();
+        let mid_word = fuzzy_match("db", "redbird").unwrap();
+        assert!(boundary > mid_word);
+    }
+
+    #[test]
+    fn fuzzy_match_rejects_non_subsequence() {
+        assert_eq!(fuzzy_match("xyz", "menu"), None);
+    }
+}